@@ -25,20 +25,85 @@ fn main() {
         .static_flag(true)
         .compile("libeaglesong.a.2");
 
+    // The CUDA kernel is built as a standalone shared library and loaded at
+    // runtime via `dlopen` (see `worker::cuda`), rather than linked into the
+    // binary. This keeps a GPU build runnable on machines without the CUDA
+    // runtime, and drops the distro-specific hardcoded `cudart` link path.
     #[cfg(feature = "gpu")]
-    cc::Build::new()
-        .file("src/worker/include/eaglesong.cu")
-        .include("src/worker/include")
-        .flag("-O3")
-        .flag("-lcrypto")
-        .cuda(true)
-        .compile("libeaglesong.a.3");
+    compile_cuda_kernel();
 
-    // Add link directory
-    // - This path depends on where you install CUDA (i.e. depends on your Linux distribution)
-    // - This should be set by `$LIBRARY_PATH`
-    #[cfg(feature = "gpu")]
-    println!("cargo:rustc-link-search=native=/usr/local/cuda/lib64");
-    #[cfg(feature = "gpu")]
-    println!("cargo:rustc-link-lib=cudart");
+    // Compile only the GPU shaders whose backend feature is enabled, so a build
+    // for one vendor does not drag in another's toolchain.
+    #[cfg(feature = "vulkan")]
+    compile_vulkan_shader();
+    #[cfg(feature = "apple_metal")]
+    compile_metal_shader();
+}
+
+// Build the CUDA kernel into `libeaglesong.so` under `OUT_DIR`, to be `dlopen`ed
+// at runtime. It is not copied next to the binary, so it is found only where the
+// dynamic loader's search path reaches it. `nvcc` links `cudart` itself, so we
+// do not.
+#[cfg(feature = "gpu")]
+fn compile_cuda_kernel() {
+    use std::path::Path;
+    use std::process::Command;
+
+    let src = "src/worker/include/eaglesong.cu";
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    let lib = Path::new(&out_dir).join("libeaglesong.so");
+    let status = Command::new("nvcc")
+        .args(["-O3", "--shared", "-Xcompiler", "-fPIC", src, "-o"])
+        .arg(&lib)
+        .status()
+        .expect("run nvcc (is the CUDA toolkit installed?)");
+    assert!(status.success(), "nvcc failed to compile {}", src);
+    println!("cargo:rerun-if-changed={}", src);
+}
+
+// Compile `eaglesong.comp` to SPIR-V with `glslc` and drop it in `OUT_DIR` for
+// `include_bytes!` in the Vulkan worker to pick up.
+#[cfg(feature = "vulkan")]
+fn compile_vulkan_shader() {
+    use std::path::Path;
+    use std::process::Command;
+
+    let src = "src/worker/include/eaglesong.comp";
+    let out = Path::new(&std::env::var("OUT_DIR").unwrap()).join("eaglesong.spv");
+    let status = Command::new("glslc")
+        .args(["-O", "-fshader-stage=compute", src, "-o"])
+        .arg(&out)
+        .status()
+        .expect("run glslc (is the Vulkan SDK installed?)");
+    assert!(status.success(), "glslc failed to compile {}", src);
+    println!("cargo:rerun-if-changed={}", src);
+}
+
+// Compile `eaglesong.metal` into a `.metallib` via the Metal toolchain.
+#[cfg(feature = "apple_metal")]
+fn compile_metal_shader() {
+    use std::path::Path;
+    use std::process::Command;
+
+    let src = "src/worker/include/eaglesong.metal";
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    let air = Path::new(&out_dir).join("eaglesong.air");
+    let lib = Path::new(&out_dir).join("eaglesong.metallib");
+
+    let status = Command::new("xcrun")
+        .args(["-sdk", "macosx", "metal", "-c", src, "-o"])
+        .arg(&air)
+        .status()
+        .expect("run `xcrun metal` (are the Xcode tools installed?)");
+    assert!(status.success(), "metal failed to compile {}", src);
+
+    let status = Command::new("xcrun")
+        .args(["-sdk", "macosx", "metallib"])
+        .arg(&air)
+        .arg("-o")
+        .arg(&lib)
+        .status()
+        .expect("run `xcrun metallib`");
+    assert!(status.success(), "metallib failed for {}", src);
+    println!("cargo:rerun-if-changed={}", src);
 }
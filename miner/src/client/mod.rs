@@ -0,0 +1,3 @@
+mod stratum;
+
+pub use stratum::StratumClient;
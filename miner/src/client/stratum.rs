@@ -0,0 +1,342 @@
+use crate::worker::WorkerMessage;
+use ckb_logger::{debug, error, info, warn};
+use ckb_types::{packed::Byte32, prelude::*, U256};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The nominal "difficulty 1" target, i.e. the target corresponding to a
+/// Stratum difficulty of `1.0`. A share difficulty of `d` maps to the target
+/// `DIFF1_TARGET / d`, which is exactly the `U256` the `solve` path compares
+/// the Eaglesong hash against.
+fn diff1_target() -> U256 {
+    // 2^256 / 2^32 == 2^224, expressed as a big-endian byte pattern.
+    let mut bytes = [0u8; 32];
+    bytes[3] = 0x1;
+    U256::from_be_bytes(&bytes)
+}
+
+/// Translate a Stratum `difficulty` into the `U256` target used by `solve`.
+fn difficulty_to_target(difficulty: f64) -> U256 {
+    if difficulty <= 0.0 {
+        return diff1_target();
+    }
+    // `U256` has no float division, so scale by a fixed factor and divide in
+    // integer space to keep enough precision for fractional difficulties.
+    const SCALE: u64 = 1_000_000;
+    let scaled = (difficulty * SCALE as f64) as u64;
+    if scaled == 0 {
+        return diff1_target();
+    }
+    diff1_target() * U256::from(SCALE) / U256::from(scaled)
+}
+
+/// A job handed down by the pool via `mining.notify`. We keep the originating
+/// job id so a later `mining.submit` can be attributed to it, and the job's
+/// `ntime` so the submitted share carries a timestamp the pool accepts. The
+/// pow-hash is the key of the `jobs` map and the target is already pushed to the
+/// workers via `NewWork`, so neither is duplicated here.
+struct Job {
+    id: String,
+    ntime: u32,
+}
+
+/// The submission-side state shared between the inbound reader and the seal
+/// drain thread. The reader installs jobs (and retires the one they replace)
+/// while the drain thread looks the active job up by pow-hash, so both touch
+/// the same map behind a lock.
+struct Submit {
+    // In-flight jobs keyed by pow-hash so a seal can be matched back to the job
+    // that produced it. A job is evicted the moment a newer `mining.notify`
+    // supersedes it, so a lookup miss here means the seal is stale.
+    jobs: HashMap<Byte32, Job>,
+    // The pow-hash of the job currently being worked; seals for anything else
+    // are stale and dropped.
+    current_job: Option<Byte32>,
+    // Offset between the server clock and ours, for accurate share timestamps.
+    time_offset: i64,
+    next_id: u64,
+    // Monotonic extranonce2 counter; each share carries a distinct value.
+    extranonce2: u64,
+}
+
+impl Submit {
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Current share timestamp, corrected by the server time offset.
+    fn share_time(&self) -> i64 {
+        local_time() + self.time_offset
+    }
+
+    /// Next extranonce2, as the hex string the pool expects in `mining.submit`.
+    fn next_extranonce2(&mut self) -> String {
+        let value = self.extranonce2;
+        self.extranonce2 += 1;
+        format!("{:08x}", value as u32)
+    }
+}
+
+/// A minimal Stratum v1 client that bridges a pool to the existing worker
+/// channels: it feeds `WorkerMessage::NewWork` down from `mining.notify` and
+/// turns seals posted on `seal_rx` back into `mining.submit`.
+pub struct StratumClient {
+    stream: TcpStream,
+    worker_tx: Sender<WorkerMessage>,
+    seal_rx: Receiver<(Byte32, u128)>,
+    user: String,
+    // Current difficulty target; seeded by `mining.set_difficulty` and applied
+    // to every subsequent `mining.notify`.
+    target: U256,
+    submit: Arc<Mutex<Submit>>,
+    // Cleared when the reader loop ends so the drain thread stops too.
+    running: Arc<AtomicBool>,
+}
+
+impl StratumClient {
+    pub fn new(
+        addr: &str,
+        user: &str,
+        worker_tx: Sender<WorkerMessage>,
+        seal_rx: Receiver<(Byte32, u128)>,
+    ) -> std::io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            worker_tx,
+            seal_rx,
+            user: user.to_owned(),
+            target: diff1_target(),
+            submit: Arc::new(Mutex::new(Submit {
+                jobs: HashMap::new(),
+                current_job: None,
+                time_offset: 0,
+                next_id: 1,
+                extranonce2: 0,
+            })),
+            running: Arc::new(AtomicBool::new(true)),
+        })
+    }
+
+    fn send(&mut self, value: &Value) -> std::io::Result<()> {
+        let mut line = value.to_string();
+        line.push('\n');
+        self.stream.write_all(line.as_bytes())
+    }
+
+    /// Perform the `mining.subscribe` / `mining.authorize` handshake.
+    fn handshake(&mut self) -> std::io::Result<()> {
+        let id = self.submit.lock().unwrap().next_id();
+        self.send(&json!({
+            "id": id,
+            "method": "mining.subscribe",
+            "params": [],
+        }))?;
+        let id = self.submit.lock().unwrap().next_id();
+        self.send(&json!({
+            "id": id,
+            "method": "mining.authorize",
+            "params": [self.user, ""],
+        }))?;
+        Ok(())
+    }
+
+    /// Drive the client until the connection is closed: parse incoming
+    /// line-delimited JSON-RPC while a dedicated thread submits seals. Decoupling
+    /// the submit path from the inbound cadence means a found share is sent at
+    /// once rather than waiting for the next server message on a quiet pool.
+    pub fn run(&mut self) -> std::io::Result<()> {
+        self.handshake()?;
+        let drain = self.spawn_drain()?;
+
+        let reader = BufReader::new(self.stream.try_clone()?);
+        for line in reader.lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Value>(&line) {
+                Ok(value) => self.handle_message(&value),
+                Err(err) => warn!("stratum: malformed message {:?}: {}", err, line),
+            }
+        }
+
+        self.running.store(false, Ordering::Relaxed);
+        let _ = drain.join();
+        Ok(())
+    }
+
+    /// Spawn the thread that forwards seals as `mining.submit`, dropping those
+    /// whose job has already been superseded by a newer `mining.notify`.
+    fn spawn_drain(&self) -> std::io::Result<thread::JoinHandle<()>> {
+        let mut stream = self.stream.try_clone()?;
+        let seal_rx = self.seal_rx.clone();
+        let submit = Arc::clone(&self.submit);
+        let running = Arc::clone(&self.running);
+        let user = self.user.clone();
+        Ok(thread::spawn(move || {
+            while running.load(Ordering::Relaxed) {
+                let (pow_hash, nonce) = match seal_rx.recv_timeout(Duration::from_millis(200)) {
+                    Ok(seal) => seal,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+                let mut guard = submit.lock().unwrap();
+                // Only the job still current with the pool is worth submitting; a
+                // seal for a job a newer `mining.notify` replaced is stale.
+                if guard.current_job.as_ref() != Some(&pow_hash) {
+                    debug!("stratum: dropping stale seal for {:x}", pow_hash);
+                    continue;
+                }
+                let (job_id, job_ntime) = match guard.jobs.get(&pow_hash) {
+                    Some(job) => (job.id.clone(), job.ntime),
+                    None => {
+                        debug!("stratum: dropping stale seal for {:x}", pow_hash);
+                        continue;
+                    }
+                };
+                let id = guard.next_id();
+                let extranonce2 = guard.next_extranonce2();
+                // Stamp in the pool's clock frame, but never before the job's own
+                // ntime so the share lands inside the acceptance window.
+                let ntime = guard.share_time().max(i64::from(job_ntime)) as u32;
+                drop(guard);
+
+                // Stratum v1 `mining.submit`:
+                // `[worker, job_id, extranonce2, ntime, nonce]`.
+                let message = json!({
+                    "id": id,
+                    "method": "mining.submit",
+                    "params": [
+                        user,
+                        job_id,
+                        extranonce2,
+                        format!("{:08x}", ntime),
+                        format!("{:032x}", nonce),
+                    ],
+                });
+                let mut line = message.to_string();
+                line.push('\n');
+                if let Err(err) = stream.write_all(line.as_bytes()) {
+                    error!("stratum: mining.submit send error {:?}", err);
+                    break;
+                }
+            }
+        }))
+    }
+
+    fn handle_message(&mut self, value: &Value) {
+        match value.get("method").and_then(Value::as_str) {
+            Some("mining.notify") => self.handle_notify(value),
+            Some("mining.set_difficulty") => self.handle_set_difficulty(value),
+            _ => debug!("stratum: ignoring message {}", value),
+        }
+    }
+
+    fn handle_set_difficulty(&mut self, value: &Value) {
+        if let Some(difficulty) = value
+            .get("params")
+            .and_then(Value::as_array)
+            .and_then(|p| p.first())
+            .and_then(Value::as_f64)
+        {
+            self.target = difficulty_to_target(difficulty);
+            info!("stratum: difficulty set to {}", difficulty);
+        }
+    }
+
+    fn handle_notify(&mut self, value: &Value) {
+        let params = match value.get("params").and_then(Value::as_array) {
+            Some(params) => params,
+            None => return,
+        };
+        let job_id = params.first().and_then(Value::as_str);
+        let blob = params.get(1).and_then(Value::as_str);
+        let (job_id, blob) = match (job_id, blob) {
+            (Some(job_id), Some(blob)) => (job_id.to_owned(), blob),
+            _ => {
+                warn!("stratum: mining.notify missing job id or header blob");
+                return;
+            }
+        };
+        let pow_hash = match decode_pow_hash(blob) {
+            Some(pow_hash) => pow_hash,
+            None => {
+                warn!("stratum: mining.notify has invalid pow-hash blob");
+                return;
+            }
+        };
+        // A per-job difficulty may override the connection-wide one.
+        let target = params
+            .get(2)
+            .and_then(Value::as_f64)
+            .map(difficulty_to_target)
+            .unwrap_or_else(|| self.target.clone());
+        // The optional `ntime` (network time, seconds) lets us learn the server's
+        // clock skew so shares are stamped in the pool's frame of reference.
+        let server_ntime = params.get(3).and_then(Value::as_i64);
+
+        {
+            let mut guard = self.submit.lock().unwrap();
+            // Retire the job this one supersedes so a seal arriving late for it is
+            // treated as stale rather than resubmitted.
+            if let Some(prev) = guard.current_job.take() {
+                if prev != pow_hash {
+                    guard.jobs.remove(&prev);
+                }
+            }
+            if let Some(ntime) = server_ntime {
+                guard.time_offset = ntime - local_time();
+            }
+            // The job's ntime is the server time in force when it arrived, echoed
+            // back on submit; fall back to our (offset-corrected) clock if absent.
+            let job_ntime = server_ntime.unwrap_or_else(|| guard.share_time()) as u32;
+            guard.jobs.insert(
+                pow_hash.clone(),
+                Job {
+                    id: job_id,
+                    ntime: job_ntime,
+                },
+            );
+            guard.current_job = Some(pow_hash.clone());
+        }
+
+        if let Err(err) = self
+            .worker_tx
+            .send(WorkerMessage::NewWork((pow_hash, target)))
+        {
+            error!("stratum: worker_tx send error {:?}", err);
+        }
+    }
+}
+
+fn local_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn decode_pow_hash(blob: &str) -> Option<Byte32> {
+    let blob = blob.strip_prefix("0x").unwrap_or(blob);
+    if blob.len() != 64 {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for (i, chunk) in blob.as_bytes().chunks(2).enumerate() {
+        let hi = (chunk[0] as char).to_digit(16)?;
+        let lo = (chunk[1] as char).to_digit(16)?;
+        bytes[i] = (hi * 16 + lo) as u8;
+    }
+    Byte32::from_slice(&bytes).ok()
+}
@@ -0,0 +1,100 @@
+use ckb_logger::warn;
+use std::fs;
+use std::path::Path;
+
+/// Default reporting interval, used when the config file omits
+/// `state_update_duration_millis`.
+pub const DEFAULT_STATE_UPDATE_DURATION_MILLIS: u128 = 300;
+
+/// Miner parameters seeded from a simple `key=value` config file, so headless
+/// and embedded deployments are reproducible without long command lines.
+///
+/// Any key may be omitted; the corresponding field keeps its auto-detected
+/// default. Unknown keys are warned about and ignored so an old binary tolerates
+/// a newer config.
+#[derive(Debug, Clone)]
+pub struct MinerConfig {
+    /// Number of CPU worker threads.
+    pub threads: usize,
+    /// Per-thread `arch` override (0 scalar / 1 avx2 / 2 avx512); the worker
+    /// clamps this down to what the CPU actually supports.
+    pub arch: u32,
+    /// Whether to attempt the opportunistic GPU worker.
+    pub gpu: bool,
+    /// Pool or node endpoint to mine against.
+    pub endpoint: Option<String>,
+    /// Base path for the per-worker nonce hashlog. When set, each worker
+    /// persists its scanned ranges so a restart resumes where it left off.
+    pub hashlog_path: Option<String>,
+    /// Progress-bar reporting interval in milliseconds for the CPU workers. The
+    /// CUDA, Vulkan and Metal workers do not read it; they report on their own
+    /// fixed 300 ms interval.
+    pub state_update_duration_millis: u128,
+}
+
+impl Default for MinerConfig {
+    fn default() -> Self {
+        Self {
+            threads: default_threads(),
+            // Request the highest kernel; the worker downgrades if unsupported.
+            arch: 2,
+            gpu: false,
+            endpoint: None,
+            hashlog_path: None,
+            state_update_duration_millis: DEFAULT_STATE_UPDATE_DURATION_MILLIS,
+        }
+    }
+}
+
+impl MinerConfig {
+    /// Load a config from `path`, falling back to the default for every key not
+    /// present in the file.
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(Self::parse(&contents))
+    }
+
+    /// Parse `key=value` lines, ignoring blanks and `#` comments. Malformed or
+    /// unknown entries are warned about and skipped rather than being fatal.
+    pub fn parse(contents: &str) -> Self {
+        let mut config = Self::default();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => {
+                    warn!("config: ignoring malformed line {:?}", line);
+                    continue;
+                }
+            };
+            match key {
+                "threads" => parse_into(key, value, &mut config.threads),
+                "arch" => parse_into(key, value, &mut config.arch),
+                "gpu" => parse_into(key, value, &mut config.gpu),
+                "endpoint" => config.endpoint = Some(value.to_owned()),
+                "hashlog_path" => config.hashlog_path = Some(value.to_owned()),
+                "state_update_duration_millis" => {
+                    parse_into(key, value, &mut config.state_update_duration_millis)
+                }
+                other => warn!("config: ignoring unknown key {:?}", other),
+            }
+        }
+        config
+    }
+}
+
+fn parse_into<T: std::str::FromStr>(key: &str, value: &str, slot: &mut T) {
+    match value.parse() {
+        Ok(parsed) => *slot = parsed,
+        Err(_) => warn!("config: invalid value {:?} for {:?}, keeping default", value, key),
+    }
+}
+
+fn default_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
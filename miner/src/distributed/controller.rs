@@ -0,0 +1,287 @@
+use super::ids::IdGenerator;
+use super::protocol::{read_message, write_message, Message};
+use ckb_logger::{debug, error, info, warn};
+use ckb_types::{packed::Byte32, prelude::*, U256};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Size of the nonce window handed out per assignment.
+const RANGE_LEN: u64 = 1 << 24;
+
+/// The seal channel a found solution is delivered on, matching the local worker
+/// seal channel so the two paths converge.
+type SealSender = Sender<(Byte32, u128)>;
+
+/// A connected worker: the channel its writer thread drains, and the ranges
+/// currently outstanding on it (so they can be recycled if it disconnects).
+struct Peer {
+    tx: Sender<Message>,
+    ranges: Vec<(u64, u128, u64)>,
+}
+
+struct State {
+    job_id: u64,
+    pow_hash: Option<Byte32>,
+    target: Option<U256>,
+    // Next nonce to hand out for the current job.
+    cursor: u128,
+    peers: HashMap<u64, Peer>,
+    // Windows freed by a disconnecting worker, handed out before fresh ones.
+    free_ranges: Vec<(u128, u64)>,
+    // Aggregate hash rate per range for the progress bar.
+    hash_rates: HashMap<u64, f64>,
+}
+
+/// The controller role: owns the current `(pow_hash, target)`, partitions the
+/// nonce space across connected workers, aggregates their hash rate, and
+/// rebroadcasts a fresh job whenever the work changes.
+#[derive(Clone)]
+pub struct Controller {
+    state: Arc<Mutex<State>>,
+    ids: Arc<IdGenerator>,
+    seal_tx: SealSender,
+}
+
+impl Controller {
+    pub fn new(seal_tx: SealSender) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                job_id: 0,
+                pow_hash: None,
+                target: None,
+                cursor: 0,
+                peers: HashMap::new(),
+                free_ranges: Vec::new(),
+                hash_rates: HashMap::new(),
+            })),
+            ids: Arc::new(IdGenerator::new()),
+            seal_tx,
+        }
+    }
+
+    /// Install a new job, abandoning in-flight ranges and rebroadcasting it to
+    /// every connected worker.
+    pub fn set_work(&self, pow_hash: Byte32, target: U256) {
+        let mut state = self.state.lock().unwrap();
+        state.job_id = self.ids.next_id();
+        state.pow_hash = Some(pow_hash.clone());
+        state.target = Some(target.clone());
+        state.cursor = 0;
+        state.free_ranges.clear();
+        state.hash_rates.clear();
+        let job = job_message(&state);
+        for peer in state.peers.values_mut() {
+            // The old job's windows are abandoned, so forget them here too;
+            // otherwise a late `Completed` for one would leave phantom ranges
+            // outstanding across the job change.
+            peer.ranges.clear();
+            let _ = peer.tx.send(job.clone());
+        }
+        // Seed each worker with its first window under the new job.
+        let peer_ids: Vec<u64> = state.peers.keys().copied().collect();
+        for peer_id in peer_ids {
+            self.assign_locked(&mut state, peer_id);
+        }
+        info!("controller broadcast job {}", state.job_id);
+    }
+
+    /// Start accepting worker connections on `addr`, spawning a handler thread
+    /// per connection. Blocks for the lifetime of the listener.
+    pub fn listen(&self, addr: &str) -> std::io::Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        info!("controller listening on {}", addr);
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let controller = self.clone();
+                    thread::spawn(move || controller.handle_peer(stream));
+                }
+                Err(err) => error!("controller accept error {:?}", err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Aggregate hash rate across all ranges, for the shared progress bar.
+    pub fn report(&self, progress_bar: &ProgressBar) {
+        let state = self.state.lock().unwrap();
+        let total: f64 = state.hash_rates.values().sum();
+        progress_bar.set_message(&format!(
+            "distributed hash rate: {:>10.3} / workers: {:>4}",
+            total,
+            state.peers.len(),
+        ));
+    }
+
+    fn handle_peer(&self, stream: TcpStream) {
+        let peer_id = self.ids.next_id();
+        if let Err(err) = stream.set_nodelay(true) {
+            warn!("set_nodelay failed {:?}", err);
+        }
+        let mut read_stream = match stream.try_clone() {
+            Ok(s) => s,
+            Err(err) => {
+                error!("clone peer stream failed {:?}", err);
+                return;
+            }
+        };
+
+        // A dedicated writer thread drains this peer's outbound channel so the
+        // controller never blocks on a slow socket while holding the lock.
+        let (tx, rx): (Sender<Message>, Receiver<Message>) = unbounded();
+        let mut write_stream = stream;
+        let writer = thread::spawn(move || {
+            for msg in rx.iter() {
+                if write_message(&mut write_stream, &msg).is_err() {
+                    break;
+                }
+            }
+        });
+
+        {
+            let mut state = self.state.lock().unwrap();
+            state.peers.insert(peer_id, Peer { tx, ranges: Vec::new() });
+            if state.pow_hash.is_some() {
+                let job = job_message(&state);
+                let _ = state.peers[&peer_id].tx.send(job);
+                self.assign_locked(&mut state, peer_id);
+            }
+        }
+
+        // Read loop: seals, heartbeats, and re-`Hello` on reconnect.
+        loop {
+            match read_message(&mut read_stream) {
+                Ok(Some(msg)) => self.handle_message(peer_id, msg),
+                Ok(None) => break,
+                Err(err) => {
+                    warn!("peer {} read error {:?}", peer_id, err);
+                    break;
+                }
+            }
+        }
+
+        self.drop_peer(peer_id);
+        drop(writer);
+    }
+
+    fn handle_message(&self, peer_id: u64, msg: Message) {
+        match msg {
+            Message::Hello => {
+                let mut state = self.state.lock().unwrap();
+                if state.pow_hash.is_some() {
+                    let job = job_message(&state);
+                    if let Some(peer) = state.peers.get(&peer_id) {
+                        let _ = peer.tx.send(job);
+                    }
+                    self.assign_locked(&mut state, peer_id);
+                }
+            }
+            Message::Seal {
+                job_id,
+                range_id: _,
+                nonce,
+            } => {
+                let state = self.state.lock().unwrap();
+                if state.job_id != job_id {
+                    debug!("dropping stale seal for job {}", job_id);
+                    return;
+                }
+                if let Some(pow_hash) = state.pow_hash.clone() {
+                    if let Err(err) = self.seal_tx.send((pow_hash, nonce)) {
+                        error!("seal_tx send error {:?}", err);
+                    }
+                }
+            }
+            Message::Heartbeat {
+                range_id,
+                hash_rate,
+            } => {
+                // A heartbeat is liveness only; it updates the aggregate hash rate
+                // but never hands out a new range, so one in-flight window cannot
+                // snowball into many.
+                let mut state = self.state.lock().unwrap();
+                state.hash_rates.insert(range_id, hash_rate);
+            }
+            Message::Completed { job_id, range_id } => {
+                let mut state = self.state.lock().unwrap();
+                // Retire the finished range so `Peer.ranges`/`hash_rates` stay
+                // bounded.
+                state.hash_rates.remove(&range_id);
+                if let Some(peer) = state.peers.get_mut(&peer_id) {
+                    peer.ranges.retain(|&(id, _, _)| id != range_id);
+                }
+                // Only hand out a replacement for a completion of the current job;
+                // a stale-job completion (after a mid-scan job switch) just retires
+                // its range, since `set_work` already seeded the new job's window.
+                if job_id == state.job_id {
+                    self.assign_locked(&mut state, peer_id);
+                }
+            }
+            other => warn!("controller received unexpected message {:?}", other),
+        }
+    }
+
+    /// Hand `peer_id` its next window, reusing a freed range if one is waiting.
+    fn assign_locked(&self, state: &mut State, peer_id: u64) {
+        if state.pow_hash.is_none() || !state.peers.contains_key(&peer_id) {
+            return;
+        }
+        let job_id = state.job_id;
+        let (nonce_start, nonce_len) = match state.free_ranges.pop() {
+            Some(range) => range,
+            None => {
+                let start = state.cursor;
+                state.cursor = state.cursor.wrapping_add(u128::from(RANGE_LEN));
+                (start, RANGE_LEN)
+            }
+        };
+        let range_id = self.ids.next_id();
+        if let Some(peer) = state.peers.get_mut(&peer_id) {
+            peer.ranges.push((range_id, nonce_start, nonce_len));
+            let _ = peer.tx.send(Message::Assign {
+                job_id,
+                range_id,
+                nonce_start,
+                nonce_len,
+            });
+        }
+    }
+
+    /// Recycle a disconnected worker's outstanding windows so another worker
+    /// picks them up.
+    fn drop_peer(&self, peer_id: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(peer) = state.peers.remove(&peer_id) {
+            for (range_id, start, len) in peer.ranges {
+                state.hash_rates.remove(&range_id);
+                state.free_ranges.push((start, len));
+            }
+            info!("worker {} disconnected, reclaimed its ranges", peer_id);
+        }
+    }
+}
+
+fn job_message(state: &State) -> Message {
+    let mut pow_hash = [0u8; 32];
+    if let Some(hash) = &state.pow_hash {
+        pow_hash.copy_from_slice(hash.as_slice());
+    }
+    let target = state
+        .target
+        .as_ref()
+        .map(|t| {
+            let mut bytes = [0u8; 32];
+            bytes.copy_from_slice(&t.to_be_bytes());
+            bytes
+        })
+        .unwrap_or([0u8; 32]);
+    Message::Job {
+        job_id: state.job_id,
+        pow_hash,
+        target,
+    }
+}
@@ -0,0 +1,26 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A monotonic id source shared across threads for job and range identifiers.
+/// Ids start at 1 so that `0` can stand for "none".
+pub struct IdGenerator {
+    next: AtomicU64,
+}
+
+impl Default for IdGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IdGenerator {
+    pub fn new() -> Self {
+        Self {
+            next: AtomicU64::new(1),
+        }
+    }
+
+    /// Return the next id, never handing out the same value twice.
+    pub fn next_id(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
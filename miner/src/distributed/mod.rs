@@ -0,0 +1,15 @@
+//! Controller/worker distribution of the nonce space across machines.
+//!
+//! A [`Controller`] holds the current `(pow_hash, target)` and hands out
+//! disjoint nonce windows to [`worker`] processes over a small length-prefixed
+//! binary [`protocol`]; workers drive the `solve` loop over exactly their
+//! assigned window and stream seals and heartbeats back.
+
+mod controller;
+mod ids;
+mod protocol;
+mod worker;
+
+pub use controller::Controller;
+pub use ids::IdGenerator;
+pub use worker::run as run_worker;
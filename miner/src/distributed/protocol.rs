@@ -0,0 +1,210 @@
+use std::io::{self, Read, Write};
+
+/// Maximum accepted frame length, a guard against a malformed length prefix
+/// asking us to allocate an absurd buffer.
+const MAX_FRAME_LEN: u32 = 1 << 20;
+
+/// Messages exchanged between the controller and its workers over a small
+/// length-prefixed binary protocol. Every frame on the wire is a big-endian
+/// `u32` length followed by that many payload bytes; the first payload byte is
+/// the tag below.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// Worker announces itself after (re)connecting.
+    Hello,
+    /// Controller pushes the current job. A worker abandons any in-flight range
+    /// whose `job_id` no longer matches.
+    Job {
+        job_id: u64,
+        pow_hash: [u8; 32],
+        target: [u8; 32],
+    },
+    /// Controller assigns a disjoint nonce window for the current job.
+    Assign {
+        job_id: u64,
+        range_id: u64,
+        nonce_start: u128,
+        nonce_len: u64,
+    },
+    /// Worker reports a found seal for the given job/range.
+    Seal {
+        job_id: u64,
+        range_id: u64,
+        nonce: u128,
+    },
+    /// Periodic liveness + hash-rate report from a worker.
+    Heartbeat { range_id: u64, hash_rate: f64 },
+    /// Worker has exhausted (or abandoned) a range and is ready for the next.
+    Completed { job_id: u64, range_id: u64 },
+}
+
+impl Message {
+    fn tag(&self) -> u8 {
+        match self {
+            Message::Hello => 0,
+            Message::Job { .. } => 1,
+            Message::Assign { .. } => 2,
+            Message::Seal { .. } => 3,
+            Message::Heartbeat { .. } => 4,
+            Message::Completed { .. } => 5,
+        }
+    }
+
+    fn encode_payload(&self, buf: &mut Vec<u8>) {
+        buf.push(self.tag());
+        match self {
+            Message::Hello => {}
+            Message::Job {
+                job_id,
+                pow_hash,
+                target,
+            } => {
+                buf.extend_from_slice(&job_id.to_be_bytes());
+                buf.extend_from_slice(pow_hash);
+                buf.extend_from_slice(target);
+            }
+            Message::Assign {
+                job_id,
+                range_id,
+                nonce_start,
+                nonce_len,
+            } => {
+                buf.extend_from_slice(&job_id.to_be_bytes());
+                buf.extend_from_slice(&range_id.to_be_bytes());
+                buf.extend_from_slice(&nonce_start.to_be_bytes());
+                buf.extend_from_slice(&nonce_len.to_be_bytes());
+            }
+            Message::Seal {
+                job_id,
+                range_id,
+                nonce,
+            } => {
+                buf.extend_from_slice(&job_id.to_be_bytes());
+                buf.extend_from_slice(&range_id.to_be_bytes());
+                buf.extend_from_slice(&nonce.to_be_bytes());
+            }
+            Message::Heartbeat {
+                range_id,
+                hash_rate,
+            } => {
+                buf.extend_from_slice(&range_id.to_be_bytes());
+                buf.extend_from_slice(&hash_rate.to_be_bytes());
+            }
+            Message::Completed { job_id, range_id } => {
+                buf.extend_from_slice(&job_id.to_be_bytes());
+                buf.extend_from_slice(&range_id.to_be_bytes());
+            }
+        }
+    }
+
+    fn decode_payload(payload: &[u8]) -> io::Result<Self> {
+        let (&tag, rest) = payload
+            .split_first()
+            .ok_or_else(|| invalid("empty frame"))?;
+        let mut cur = Cursor::new(rest);
+        let msg = match tag {
+            0 => Message::Hello,
+            1 => Message::Job {
+                job_id: cur.u64()?,
+                pow_hash: cur.array32()?,
+                target: cur.array32()?,
+            },
+            2 => Message::Assign {
+                job_id: cur.u64()?,
+                range_id: cur.u64()?,
+                nonce_start: cur.u128()?,
+                nonce_len: cur.u64()?,
+            },
+            3 => Message::Seal {
+                job_id: cur.u64()?,
+                range_id: cur.u64()?,
+                nonce: cur.u128()?,
+            },
+            4 => Message::Heartbeat {
+                range_id: cur.u64()?,
+                hash_rate: f64::from_be_bytes(cur.array8()?),
+            },
+            5 => Message::Completed {
+                job_id: cur.u64()?,
+                range_id: cur.u64()?,
+            },
+            other => return Err(invalid(&format!("unknown message tag {}", other))),
+        };
+        Ok(msg)
+    }
+}
+
+/// Write a single framed message to `w`.
+pub fn write_message<W: Write>(w: &mut W, msg: &Message) -> io::Result<()> {
+    let mut payload = Vec::new();
+    msg.encode_payload(&mut payload);
+    let len = payload.len() as u32;
+    w.write_all(&len.to_be_bytes())?;
+    w.write_all(&payload)?;
+    w.flush()
+}
+
+/// Read a single framed message from `r`, returning `None` on a clean EOF.
+pub fn read_message<R: Read>(r: &mut R) -> io::Result<Option<Message>> {
+    let mut len_buf = [0u8; 4];
+    match r.read_exact(&mut len_buf) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let len = u32::from_be_bytes(len_buf);
+    if len > MAX_FRAME_LEN {
+        return Err(invalid("frame length exceeds maximum"));
+    }
+    let mut payload = vec![0u8; len as usize];
+    r.read_exact(&mut payload)?;
+    Message::decode_payload(&payload).map(Some)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
+
+/// A tiny big-endian reader over a byte slice.
+struct Cursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let end = self.pos + n;
+        if end > self.buf.len() {
+            return Err(invalid("frame truncated"));
+        }
+        let slice = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_be_bytes(self.array8()?))
+    }
+
+    fn u128(&mut self) -> io::Result<u128> {
+        let mut arr = [0u8; 16];
+        arr.copy_from_slice(self.take(16)?);
+        Ok(u128::from_be_bytes(arr))
+    }
+
+    fn array8(&mut self) -> io::Result<[u8; 8]> {
+        let mut arr = [0u8; 8];
+        arr.copy_from_slice(self.take(8)?);
+        Ok(arr)
+    }
+
+    fn array32(&mut self) -> io::Result<[u8; 32]> {
+        let mut arr = [0u8; 32];
+        arr.copy_from_slice(self.take(32)?);
+        Ok(arr)
+    }
+}
@@ -0,0 +1,186 @@
+use super::protocol::{read_message, write_message, Message};
+use ckb_logger::{error, info, warn};
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The length of each sub-window the worker scans between heartbeats, so a
+/// large assigned range still produces regular liveness reports and a fresh job
+/// is noticed promptly.
+const SCAN_CHUNK: u64 = 1 << 20;
+
+// The scalar kernel, invoked directly over the controller-assigned window. The
+// explicit `nonce_start`/`nonce_len` window is exactly what the distributed
+// mode needs to confine a worker to its slice of the nonce space.
+extern "C" {
+    fn c_solve(
+        input: *const u8,
+        target: *const u8,
+        nonce_start: *const u8,
+        nonce_len: u64,
+        nonce: *mut u8,
+    ) -> u32;
+}
+
+/// Run the worker role: connect to `controller_addr`, drive the assigned nonce
+/// windows through `solve`, and stream seals and heartbeats back. On a dropped
+/// connection it retries with a fixed backoff so a restarted controller is
+/// picked back up automatically.
+pub fn run(controller_addr: &str) {
+    loop {
+        match connect_and_serve(controller_addr) {
+            Ok(()) => info!("controller closed connection, reconnecting"),
+            Err(err) => warn!("controller connection error {:?}, reconnecting", err),
+        }
+        thread::sleep(Duration::from_secs(3));
+    }
+}
+
+fn connect_and_serve(controller_addr: &str) -> std::io::Result<()> {
+    let mut stream = TcpStream::connect(controller_addr)?;
+    stream.set_nodelay(true)?;
+    write_message(&mut stream, &Message::Hello)?;
+    info!("connected to controller at {}", controller_addr);
+
+    // The latest job id the controller has announced, updated by the reader
+    // thread the instant a `Job` arrives. `scan_window` consults it between
+    // chunks so a stale window is abandoned without waiting for it to finish.
+    let current_job = Arc::new(AtomicU64::new(0));
+    let (tx, rx) = mpsc::channel();
+    let reader_stream = stream.try_clone()?;
+    let reader_job = Arc::clone(&current_job);
+    let reader = thread::spawn(move || reader_loop(reader_stream, tx, reader_job));
+
+    let mut job: Option<([u8; 32], [u8; 32], u64)> = None;
+    for msg in rx {
+        match msg {
+            Message::Job {
+                job_id,
+                pow_hash,
+                target,
+            } => {
+                // A new job abandons any in-flight range from the old one; the
+                // reader has already bumped `current_job`.
+                job = Some((pow_hash, target, job_id));
+            }
+            Message::Assign {
+                job_id,
+                range_id,
+                nonce_start,
+                nonce_len,
+            } => {
+                if let Some((pow_hash, target, current)) = job {
+                    if current != job_id {
+                        // Stale assignment for a job we have already moved past.
+                        continue;
+                    }
+                    scan_window(
+                        &mut stream,
+                        &pow_hash,
+                        &target,
+                        job_id,
+                        range_id,
+                        nonce_start,
+                        nonce_len,
+                        &current_job,
+                    )?;
+                    // Ask the controller for the next window; it also retires this
+                    // range so its bookkeeping stays bounded.
+                    write_message(&mut stream, &Message::Completed { job_id, range_id })?;
+                }
+            }
+            other => warn!("worker received unexpected message {:?}", other),
+        }
+    }
+
+    let _ = reader.join();
+    Ok(())
+}
+
+/// Read framed messages until EOF, forwarding them to the serve loop and
+/// recording the newest job id so an in-flight scan can see it immediately.
+fn reader_loop(mut stream: TcpStream, tx: mpsc::Sender<Message>, current_job: Arc<AtomicU64>) {
+    loop {
+        match read_message(&mut stream) {
+            Ok(Some(msg)) => {
+                if let Message::Job { job_id, .. } = &msg {
+                    current_job.store(*job_id, Ordering::Relaxed);
+                }
+                if tx.send(msg).is_err() {
+                    break;
+                }
+            }
+            Ok(None) => break,
+            Err(err) => {
+                warn!("controller read error {:?}", err);
+                break;
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn scan_window(
+    stream: &mut TcpStream,
+    pow_hash: &[u8; 32],
+    target: &[u8; 32],
+    job_id: u64,
+    range_id: u64,
+    nonce_start: u128,
+    nonce_len: u64,
+    current_job: &AtomicU64,
+) -> std::io::Result<()> {
+    let mut scanned = 0u64;
+    while scanned < nonce_len {
+        // Bail out of a window the controller has already replaced so stale work
+        // is not ground through to completion.
+        if current_job.load(Ordering::Relaxed) != job_id {
+            break;
+        }
+        let chunk = SCAN_CHUNK.min(nonce_len - scanned);
+        let start = nonce_start.wrapping_add(u128::from(scanned));
+        let start_bytes = start.to_le_bytes();
+        let mut nonce = [0u8; 16];
+        let chunk_start = Instant::now();
+        let ns = unsafe {
+            c_solve(
+                pow_hash.as_ptr(),
+                target.as_ptr(),
+                start_bytes.as_ptr(),
+                chunk,
+                nonce.as_mut_ptr(),
+            )
+        };
+        let elapsed = chunk_start.elapsed();
+        let nonce = u128::from_le_bytes(nonce);
+        if nonce != 0 {
+            if let Err(err) = write_message(
+                stream,
+                &Message::Seal {
+                    job_id,
+                    range_id,
+                    nonce,
+                },
+            ) {
+                error!("failed to report seal {:?}", err);
+            }
+        }
+        scanned += chunk;
+        // Report an actual rate (nonces per second), mirroring the CPU worker, so
+        // the controller's aggregate is a hash rate rather than a raw count.
+        let elapsed_secs = (elapsed.as_secs() * 1_000_000_000 + u64::from(elapsed.subsec_nanos()))
+            as f64
+            / 1_000_000_000.0;
+        let hash_rate = if elapsed_secs > 0.0 {
+            ns as f64 / elapsed_secs
+        } else {
+            0.0
+        };
+        write_message(stream, &Message::Heartbeat { range_id, hash_rate })?;
+    }
+    stream.flush()
+}
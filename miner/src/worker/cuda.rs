@@ -0,0 +1,181 @@
+use super::{Worker, WorkerMessage};
+use ckb_logger::{debug, error, info, warn};
+use ckb_types::{packed::Byte32, prelude::*, U256};
+use crossbeam_channel::{Receiver, Sender};
+use indicatif::ProgressBar;
+use libloading::Library;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const STATE_UPDATE_DURATION_MILLIS: u128 = 300;
+
+/// Nonce window scanned per kernel launch. Bounding it keeps each FFI call
+/// short so the report/stop loop runs between launches instead of the kernel
+/// swallowing the whole nonce space in one blocking call. The GPU chews through
+/// far more per launch than a CPU core, so the window is correspondingly wider.
+const NONCE_WINDOW: u64 = 1 << 26;
+
+// The CUDA kernel entry point, matching the CPU `c_solve` signature including
+// the explicit nonce window introduced for checkpointing.
+type CSolveCudaFn = unsafe extern "C" fn(
+    input: *const u8,
+    target: *const u8,
+    nonce_start: *const u8,
+    nonce_len: u64,
+    nonce: *mut u8,
+) -> u32;
+
+/// A GPU worker backed by the CUDA kernel loaded at runtime. The `Library`
+/// handles are kept alive for the lifetime of the worker so the resolved
+/// symbol stays valid.
+pub struct EaglesongCuda {
+    start: bool,
+    pow_info: Option<(Byte32, U256)>,
+    seal_tx: Sender<(Byte32, u128)>,
+    worker_rx: Receiver<WorkerMessage>,
+    seal_candidates_found: u64,
+    // Base nonce for the next launch, advanced by `NONCE_WINDOW` each pass and
+    // reset when new work arrives.
+    nonce_base: u128,
+    // Resolved once in `try_new`; the owning `Library` below keeps it valid.
+    solve_fn: CSolveCudaFn,
+    _cudart: Library,
+    _kernel: Library,
+}
+
+impl EaglesongCuda {
+    /// Attempt to load the CUDA runtime and the compiled Eaglesong kernel. Returns
+    /// `None` (after logging) when either library or the kernel symbol cannot be
+    /// resolved, so the caller can fall back to the CPU workers instead of
+    /// failing to start.
+    pub fn try_new(
+        seal_tx: Sender<(Byte32, u128)>,
+        worker_rx: Receiver<WorkerMessage>,
+    ) -> Option<Self> {
+        let cudart = match unsafe { load_any(&["libcudart.so", "libcudart.dylib", "cudart.dll"]) } {
+            Some(lib) => lib,
+            None => {
+                info!("libcudart not found, GPU mining disabled");
+                return None;
+            }
+        };
+        let kernel = match unsafe {
+            load_any(&["libeaglesong.so", "libeaglesong.dylib", "eaglesong.dll"])
+        } {
+            Some(lib) => lib,
+            None => {
+                warn!("CUDA runtime present but eaglesong kernel not found, GPU mining disabled");
+                return None;
+            }
+        };
+        // Resolve the kernel symbol once, up front: if it is missing we fall back
+        // to the CPU path, and if it resolves we keep the function pointer so the
+        // hot loop never re-resolves it. The `kernel` library is retained below so
+        // the pointer stays valid for the worker's lifetime.
+        let solve_fn = match unsafe { kernel.get::<CSolveCudaFn>(b"c_solve_cuda\0") } {
+            Ok(sym) => unsafe { *sym },
+            Err(_) => {
+                warn!("eaglesong kernel missing `c_solve_cuda`, GPU mining disabled");
+                return None;
+            }
+        };
+        info!("CUDA kernel loaded, enabling GPU worker");
+        Some(Self {
+            start: true,
+            pow_info: None,
+            seal_tx,
+            worker_rx,
+            seal_candidates_found: 0,
+            nonce_base: 0,
+            solve_fn,
+            _cudart: cudart,
+            _kernel: kernel,
+        })
+    }
+
+    fn poll_worker_message(&mut self) {
+        if let Ok(msg) = self.worker_rx.try_recv() {
+            match msg {
+                WorkerMessage::NewWork(pow_info) => {
+                    self.pow_info = Some(pow_info);
+                    self.nonce_base = 0;
+                }
+                WorkerMessage::Stop => self.start = false,
+                WorkerMessage::Start => self.start = true,
+            }
+        }
+    }
+
+    fn solve(&mut self, pow_hash: &Byte32, target: &U256) -> usize {
+        unsafe {
+            let nonce_start = self.nonce_base.to_le_bytes();
+            self.nonce_base = self.nonce_base.wrapping_add(u128::from(NONCE_WINDOW));
+            let mut nonce = [0u8; 16];
+            let ns = (self.solve_fn)(
+                pow_hash.as_slice().as_ptr(),
+                target.to_be_bytes().as_ptr(),
+                nonce_start.as_ptr(),
+                NONCE_WINDOW,
+                nonce.as_mut_ptr(),
+            );
+            let nonce = u128::from_le_bytes(nonce);
+            if nonce != 0 {
+                debug!(
+                    "send new found seal, pow_hash {:x}, nonce {:?}",
+                    pow_hash, nonce
+                );
+                if let Err(err) = self.seal_tx.send((pow_hash.clone(), nonce)) {
+                    error!("seal_tx send error {:?}", err);
+                }
+                self.seal_candidates_found += 1;
+            }
+            ns as usize
+        }
+    }
+}
+
+impl Worker for EaglesongCuda {
+    fn run(&mut self, progress_bar: &ProgressBar) {
+        let mut state_update_counter = 0usize;
+        let mut start = Instant::now();
+        loop {
+            self.poll_worker_message();
+            if self.start {
+                if let Some((pow_hash, target)) = self.pow_info.clone() {
+                    state_update_counter += self.solve(&pow_hash, &target);
+
+                    let elapsed = start.elapsed();
+                    if elapsed.as_millis() > STATE_UPDATE_DURATION_MILLIS {
+                        let elapsed_nanos: f64 = (elapsed.as_secs() * 1_000_000_000
+                            + u64::from(elapsed.subsec_nanos()))
+                            as f64
+                            / 1_000_000_000.0;
+                        progress_bar.set_message(&format!(
+                            "cuda hash rate: {:>10.3} / seals found: {:>10}",
+                            state_update_counter as f64 / elapsed_nanos,
+                            self.seal_candidates_found,
+                        ));
+                        progress_bar.inc(1);
+                        state_update_counter = 0;
+                        start = Instant::now();
+                    }
+                }
+            } else {
+                state_update_counter = 0;
+                start = Instant::now();
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+/// Try each candidate library name in turn, returning the first that loads.
+unsafe fn load_any(names: &[&str]) -> Option<Library> {
+    for name in names {
+        match Library::new(name) {
+            Ok(lib) => return Some(lib),
+            Err(err) => debug!("dlopen {} failed: {:?}", name, err),
+        }
+    }
+    None
+}
@@ -1,17 +1,86 @@
+use super::hashlog::HashLog;
 use super::{Worker, WorkerMessage};
-use ckb_logger::{debug, error};
+use ckb_logger::{debug, error, info, warn};
 use ckb_types::{packed::Byte32, prelude::*, U256};
 use crossbeam_channel::{Receiver, Sender};
 use indicatif::ProgressBar;
 use std::thread;
 use std::time::{Duration, Instant};
 
-const STATE_UPDATE_DURATION_MILLIS: u128 = 300;
+// Kernel selectors as accepted on the command line / config file.
+const ARCH_SCALAR: u32 = 0;
+const ARCH_AVX2: u32 = 1;
+const ARCH_AVX512: u32 = 2;
 
 extern "C" {
-    pub fn c_solve(input: *const u8, target: *const u8, nonce: *mut u8) -> u32;
-    pub fn c_solve_avx2(input: *const u8, target: *const u8, nonce: *mut u8) -> u32;
-    pub fn c_solve_avx512(input: *const u8, target: *const u8, nonce: *mut u8) -> u32;
+    pub fn c_solve(
+        input: *const u8,
+        target: *const u8,
+        nonce_start: *const u8,
+        nonce_len: u64,
+        nonce: *mut u8,
+    ) -> u32;
+    pub fn c_solve_avx2(
+        input: *const u8,
+        target: *const u8,
+        nonce_start: *const u8,
+        nonce_len: u64,
+        nonce: *mut u8,
+    ) -> u32;
+    pub fn c_solve_avx512(
+        input: *const u8,
+        target: *const u8,
+        nonce_start: *const u8,
+        nonce_len: u64,
+        nonce: *mut u8,
+    ) -> u32;
+}
+
+/// Probe the CPU at runtime and clamp the requested `arch` down to the best
+/// kernel the host can actually execute.
+///
+/// The caller may ask for a kernel that the hardware does not provide (a stale
+/// config copied between machines, an optimistic default); dispatching to it
+/// anyway faults with an illegal instruction. We instead walk the
+/// `avx512 -> avx2 -> scalar` chain, picking the highest kernel that is both
+/// requested and supported, and log whenever the request is downgraded so the
+/// misconfiguration is visible rather than fatal.
+fn resolve_arch(requested: u32) -> u32 {
+    let available = best_available_arch();
+    if requested <= available {
+        requested
+    } else {
+        warn!(
+            "requested arch {} is not supported on this CPU, downgrading to {}",
+            requested, available
+        );
+        available
+    }
+}
+
+/// Highest kernel the current CPU can run, detected at runtime.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn best_available_arch() -> u32 {
+    if std::arch::is_x86_feature_detected!("avx512f") {
+        ARCH_AVX512
+    } else if std::arch::is_x86_feature_detected!("avx2") {
+        ARCH_AVX2
+    } else {
+        ARCH_SCALAR
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+fn best_available_arch() -> u32 {
+    ARCH_SCALAR
+}
+
+fn arch_name(arch: u32) -> &'static str {
+    match arch {
+        ARCH_AVX512 => "avx512",
+        ARCH_AVX2 => "avx2",
+        _ => "scalar",
+    }
 }
 
 pub struct EaglesongCpu {
@@ -21,6 +90,8 @@ pub struct EaglesongCpu {
     worker_rx: Receiver<WorkerMessage>,
     seal_candidates_found: u64,
     arch: u32,
+    hashlog: HashLog,
+    state_update_duration_millis: u128,
 }
 
 impl EaglesongCpu {
@@ -28,7 +99,17 @@ impl EaglesongCpu {
         seal_tx: Sender<(Byte32, u128)>,
         worker_rx: Receiver<WorkerMessage>,
         arch: u32,
+        state_update_duration_millis: u128,
+        hashlog_path: Option<String>,
     ) -> Self {
+        let arch = resolve_arch(arch);
+        info!("eaglesong cpu worker using {} kernel", arch_name(arch));
+        // A path-backed log resumes the scanned nonce ranges from a previous run;
+        // without one the log stays purely in-memory for the job-refresh case.
+        let hashlog = match hashlog_path {
+            Some(path) => HashLog::persistent(path),
+            None => HashLog::new(),
+        };
         Self {
             start: true,
             pow_info: None,
@@ -36,6 +117,8 @@ impl EaglesongCpu {
             seal_tx,
             worker_rx,
             arch,
+            hashlog,
+            state_update_duration_millis,
         }
     }
 
@@ -57,26 +140,38 @@ impl EaglesongCpu {
 
     #[inline]
     fn solve(&mut self, pow_hash: &Byte32, target: &U256) -> usize {
+        // Resume from the first nonce range that has not already been exhausted
+        // for this pow-hash, so re-broadcast or post-restart work does not redo
+        // finished territory.
+        let (nonce_start, nonce_len) = self.hashlog.next_window(pow_hash);
+        let nonce_start_bytes = nonce_start.to_le_bytes();
         unsafe {
             let mut nonce = [0u8; 16];
             let ns = match self.arch {
-                0 => c_solve(
+                ARCH_AVX512 => c_solve_avx512(
                     pow_hash.as_slice().as_ptr(),
                     target.to_be_bytes().as_ptr(),
+                    nonce_start_bytes.as_ptr(),
+                    nonce_len,
                     nonce.as_mut_ptr(),
                 ),
-                1 => c_solve_avx2(
+                ARCH_AVX2 => c_solve_avx2(
                     pow_hash.as_slice().as_ptr(),
                     target.to_be_bytes().as_ptr(),
+                    nonce_start_bytes.as_ptr(),
+                    nonce_len,
                     nonce.as_mut_ptr(),
                 ),
-                2 => c_solve_avx512(
+                _ => c_solve(
                     pow_hash.as_slice().as_ptr(),
                     target.to_be_bytes().as_ptr(),
+                    nonce_start_bytes.as_ptr(),
+                    nonce_len,
                     nonce.as_mut_ptr(),
                 ),
-                _ => unreachable!(),
             };
+            // Record the window we just scanned so the next poll advances past it.
+            self.hashlog.record(pow_hash, nonce_start, ns as u64);
             let nonce = u128::from_le_bytes(nonce);
             if nonce != 0 {
                 debug!(
@@ -105,13 +200,14 @@ impl Worker for EaglesongCpu {
                     state_update_counter += self.solve(&pow_hash, &target);
 
                     let elapsed = start.elapsed();
-                    if elapsed.as_millis() > STATE_UPDATE_DURATION_MILLIS {
+                    if elapsed.as_millis() > self.state_update_duration_millis {
                         let elapsed_nanos: f64 = (elapsed.as_secs() * 1_000_000_000
                             + u64::from(elapsed.subsec_nanos()))
                             as f64
                             / 1_000_000_000.0;
                         progress_bar.set_message(&format!(
-                            "hash rate: {:>10.3} / seals found: {:>10}",
+                            "{} hash rate: {:>10.3} / seals found: {:>10}",
+                            arch_name(self.arch),
                             state_update_counter as f64 / elapsed_nanos,
                             self.seal_candidates_found,
                         ));
@@ -0,0 +1,231 @@
+use ckb_logger::warn;
+use ckb_types::{packed::Byte32, prelude::*};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Default size of the nonce window handed to a single `solve` call. The kernel
+/// scans `[start, start + WINDOW_LEN)` and reports back how much of it it
+/// actually consumed.
+pub const WINDOW_LEN: u64 = 1 << 20;
+
+/// How many recent pow-hashes to keep ranges for. Old work is never re-scanned
+/// once a pool has churned this many jobs past it, so evicting the least
+/// recently touched hash bounds both memory and the persisted file without
+/// losing the resume guarantee for current and recent work.
+const MAX_TRACKED: usize = 64;
+
+/// The contiguous nonce ranges already scanned for a single pow-hash, kept
+/// sorted and coalesced so the log stays compact across frequent job churn.
+#[derive(Default)]
+struct Ranges {
+    // Half-open `[start, end)` intervals, sorted by `start`, non-overlapping and
+    // non-adjacent (adjacent intervals are merged on insert).
+    spans: Vec<(u128, u128)>,
+}
+
+impl Ranges {
+    /// Record that `[start, start + len)` has been scanned, merging it with any
+    /// abutting or overlapping spans already present.
+    fn record(&mut self, start: u128, len: u64) {
+        if len == 0 {
+            return;
+        }
+        let end = start.saturating_add(u128::from(len));
+        let mut merged = (start, end);
+        let mut out = Vec::with_capacity(self.spans.len() + 1);
+        let mut inserted = false;
+        for &(s, e) in &self.spans {
+            if e < merged.0 {
+                // Strictly before the new span, with a gap.
+                out.push((s, e));
+            } else if s > merged.1 {
+                // Strictly after, with a gap; the new span is now final.
+                if !inserted {
+                    out.push(merged);
+                    inserted = true;
+                }
+                out.push((s, e));
+            } else {
+                // Overlaps or abuts: absorb into the running merge.
+                merged.0 = merged.0.min(s);
+                merged.1 = merged.1.max(e);
+            }
+        }
+        if !inserted {
+            out.push(merged);
+        }
+        self.spans = out;
+    }
+
+    /// The first nonce at or above `from` that has not yet been scanned.
+    fn next_unscanned(&self, from: u128) -> u128 {
+        let mut cursor = from;
+        for &(s, e) in &self.spans {
+            if e <= cursor {
+                continue;
+            }
+            if s > cursor {
+                break;
+            }
+            // `cursor` falls inside this span; skip past it.
+            cursor = e;
+        }
+        cursor
+    }
+}
+
+/// Per-pow-hash record of exhausted nonce ranges, so identical work (a node
+/// re-broadcast, or a restart against a persisted log) resumes from the first
+/// un-scanned range rather than recomputing finished territory.
+///
+/// When constructed with [`HashLog::persistent`] the log is seeded from the
+/// file on disk and flushed back on drop, so the resume guarantee spans process
+/// restarts and not just in-process job refreshes.
+#[derive(Default)]
+pub struct HashLog {
+    by_hash: HashMap<Byte32, Ranges>,
+    // Pow-hashes in least-to-most-recently-touched order, bounding `by_hash` to
+    // [`MAX_TRACKED`] entries so frequent job churn does not grow it without end.
+    order: Vec<Byte32>,
+    path: Option<PathBuf>,
+}
+
+impl HashLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a log backed by `path`, loading any ranges already persisted there.
+    /// A missing file is treated as an empty log; a malformed one is warned
+    /// about line by line and otherwise tolerated, matching the config loader.
+    pub fn persistent<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let mut log = match fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                warn!("hashlog: cannot read {:?}: {:?}", path, err);
+                Self::default()
+            }
+        };
+        log.path = Some(path);
+        log
+    }
+
+    /// The next window to scan for `pow_hash`: the first un-scanned nonce and a
+    /// length bounded by [`WINDOW_LEN`].
+    pub fn next_window(&self, pow_hash: &Byte32) -> (u128, u64) {
+        let start = self
+            .by_hash
+            .get(pow_hash)
+            .map(|r| r.next_unscanned(0))
+            .unwrap_or(0);
+        (start, WINDOW_LEN)
+    }
+
+    /// Mark `[start, start + len)` as scanned for `pow_hash`.
+    pub fn record(&mut self, pow_hash: &Byte32, start: u128, len: u64) {
+        self.touch(pow_hash);
+        self.by_hash
+            .entry(pow_hash.clone())
+            .or_default()
+            .record(start, len);
+    }
+
+    /// Move `pow_hash` to the most-recent end of the LRU order, evicting the
+    /// least recently touched hashes once the cap is exceeded.
+    fn touch(&mut self, pow_hash: &Byte32) {
+        if let Some(pos) = self.order.iter().position(|h| h == pow_hash) {
+            self.order.remove(pos);
+        }
+        self.order.push(pow_hash.clone());
+        while self.order.len() > MAX_TRACKED {
+            let evicted = self.order.remove(0);
+            self.by_hash.remove(&evicted);
+        }
+    }
+
+    /// Flush the log to the file given at construction, one line per pow-hash:
+    /// the 32-byte hash in hex followed by its `start:end` spans, compact and
+    /// trivially re-parsed by [`HashLog::parse`]. A no-op for an in-memory log
+    /// created via [`HashLog::new`].
+    pub fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut file = fs::File::create(path)?;
+        for (pow_hash, ranges) in &self.by_hash {
+            if ranges.spans.is_empty() {
+                continue;
+            }
+            write!(file, "{:x}", pow_hash)?;
+            for (start, end) in &ranges.spans {
+                write!(file, " {}:{}", start, end)?;
+            }
+            writeln!(file)?;
+        }
+        file.flush()
+    }
+
+    fn parse(contents: &str) -> Self {
+        let mut log = Self::default();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let pow_hash = match fields.next().and_then(decode_hash) {
+                Some(pow_hash) => pow_hash,
+                None => {
+                    warn!("hashlog: ignoring line with invalid pow-hash {:?}", line);
+                    continue;
+                }
+            };
+            let mut ranges = Ranges::default();
+            for span in fields {
+                match parse_span(span) {
+                    Some((start, end)) if end > start => {
+                        ranges.record(start, (end - start) as u64)
+                    }
+                    _ => warn!("hashlog: ignoring malformed span {:?}", span),
+                }
+            }
+            log.touch(&pow_hash);
+            log.by_hash.insert(pow_hash, ranges);
+        }
+        log
+    }
+}
+
+impl Drop for HashLog {
+    fn drop(&mut self) {
+        if self.path.is_some() {
+            if let Err(err) = self.save() {
+                warn!("hashlog: failed to persist log: {:?}", err);
+            }
+        }
+    }
+}
+
+fn parse_span(span: &str) -> Option<(u128, u128)> {
+    let (start, end) = span.split_once(':')?;
+    Some((start.parse().ok()?, end.parse().ok()?))
+}
+
+fn decode_hash(hex: &str) -> Option<Byte32> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for (i, chunk) in hex.as_bytes().chunks(2).enumerate() {
+        let hi = (chunk[0] as char).to_digit(16)?;
+        let lo = (chunk[1] as char).to_digit(16)?;
+        bytes[i] = (hi * 16 + lo) as u8;
+    }
+    Byte32::from_slice(&bytes).ok()
+}
@@ -0,0 +1,200 @@
+use super::{Worker, WorkerMessage};
+use ckb_logger::{debug, error, info};
+use ckb_types::{packed::Byte32, prelude::*, U256};
+use crossbeam_channel::{Receiver, Sender};
+use indicatif::ProgressBar;
+use metal::{
+    CommandQueue, ComputePipelineState, Device as MetalDevice, MTLResourceOptions, MTLSize,
+};
+use std::ffi::c_void;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const STATE_UPDATE_DURATION_MILLIS: u128 = 300;
+
+/// Eaglesong invocations dispatched per command buffer. Each thread takes the
+/// base nonce plus its global index, so one dispatch covers `DISPATCH_SIZE`
+/// consecutive nonces.
+const DISPATCH_SIZE: u64 = 1 << 20;
+
+/// Metal shader library for the Eaglesong round function, compiled from
+/// `src/worker/include/eaglesong.metal` by `build.rs` when the `apple_metal`
+/// feature is enabled.
+const EAGLESONG_METALLIB: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/eaglesong.metallib"));
+
+/// A GPU worker that dispatches the Eaglesong compute shader on Apple Silicon
+/// via Metal, giving M-series Macs a native mining backend.
+pub struct EaglesongMetal {
+    start: bool,
+    pow_info: Option<(Byte32, U256)>,
+    seal_tx: Sender<(Byte32, u128)>,
+    worker_rx: Receiver<WorkerMessage>,
+    seal_candidates_found: u64,
+    // Base nonce for the next dispatch, advanced by `DISPATCH_SIZE` each pass and
+    // reset when new work arrives so consecutive dispatches cover fresh space.
+    nonce_base: u128,
+    device: MetalDevice,
+    queue: CommandQueue,
+    pipeline: ComputePipelineState,
+}
+
+impl EaglesongMetal {
+    pub fn new(
+        seal_tx: Sender<(Byte32, u128)>,
+        worker_rx: Receiver<WorkerMessage>,
+    ) -> Result<Self, String> {
+        let device = MetalDevice::system_default().ok_or_else(|| "no metal device".to_string())?;
+        let library = device
+            .new_library_with_data(EAGLESONG_METALLIB)
+            .map_err(|e| format!("load metal library: {}", e))?;
+        let function = library
+            .get_function("eaglesong_solve", None)
+            .map_err(|e| format!("get metal function: {}", e))?;
+        let pipeline = device
+            .new_compute_pipeline_state_with_function(&function)
+            .map_err(|e| format!("create metal pipeline: {}", e))?;
+        let queue = device.new_command_queue();
+        info!("eaglesong metal worker initialized");
+        Ok(Self {
+            start: true,
+            pow_info: None,
+            seal_tx,
+            worker_rx,
+            seal_candidates_found: 0,
+            nonce_base: 0,
+            device,
+            queue,
+            pipeline,
+        })
+    }
+
+    fn poll_worker_message(&mut self) {
+        if let Ok(msg) = self.worker_rx.try_recv() {
+            match msg {
+                WorkerMessage::NewWork(pow_info) => {
+                    self.pow_info = Some(pow_info);
+                    self.nonce_base = 0;
+                }
+                WorkerMessage::Stop => self.start = false,
+                WorkerMessage::Start => self.start = true,
+            }
+        }
+    }
+
+    fn solve(&mut self, pow_hash: &Byte32, target: &U256) -> usize {
+        let base = self.nonce_base;
+        self.nonce_base = self.nonce_base.wrapping_add(u128::from(DISPATCH_SIZE));
+        match self.dispatch(pow_hash, target, base, DISPATCH_SIZE) {
+            Some(nonce) => {
+                debug!(
+                    "send new found seal, pow_hash {:x}, nonce {:?}",
+                    pow_hash, nonce
+                );
+                if let Err(err) = self.seal_tx.send((pow_hash.clone(), nonce)) {
+                    error!("seal_tx send error {:?}", err);
+                }
+                self.seal_candidates_found += 1;
+                DISPATCH_SIZE as usize
+            }
+            None => DISPATCH_SIZE as usize,
+        }
+    }
+
+    /// Bind the pow-hash, target and base nonce, dispatch `count` threads, and
+    /// read the result buffer back, returning the winning nonce the shader wrote
+    /// (non-zero) if one met `target`.
+    fn dispatch(&self, pow_hash: &Byte32, target: &U256, base: u128, count: u64) -> Option<u128> {
+        let shared = MTLResourceOptions::StorageModeShared;
+        let target_bytes = target.to_be_bytes();
+        let base_bytes = base.to_le_bytes();
+
+        let input = self.device.new_buffer_with_data(
+            pow_hash.as_slice().as_ptr() as *const c_void,
+            32,
+            shared,
+        );
+        let target_buf =
+            self.device
+                .new_buffer_with_data(target_bytes.as_ptr() as *const c_void, 32, shared);
+        let base_buf =
+            self.device
+                .new_buffer_with_data(base_bytes.as_ptr() as *const c_void, 16, shared);
+        // Zero-initialised; the shader writes the winning nonce here, or leaves
+        // it zero if none of its threads met the target.
+        let result = self.device.new_buffer(16, shared);
+
+        let command_buffer = self.queue.new_command_buffer();
+        let encoder = command_buffer.new_compute_command_encoder();
+        encoder.set_compute_pipeline_state(&self.pipeline);
+        encoder.set_buffer(0, Some(&input), 0);
+        encoder.set_buffer(1, Some(&target_buf), 0);
+        encoder.set_buffer(2, Some(&base_buf), 0);
+        encoder.set_buffer(3, Some(&result), 0);
+
+        let width = self
+            .pipeline
+            .thread_execution_width()
+            .max(1)
+            .min(count.max(1));
+        let threads_per_group = MTLSize {
+            width,
+            height: 1,
+            depth: 1,
+        };
+        let groups = MTLSize {
+            width: (count + width - 1) / width,
+            height: 1,
+            depth: 1,
+        };
+        encoder.dispatch_thread_groups(groups, threads_per_group);
+        encoder.end_encoding();
+        command_buffer.commit();
+        command_buffer.wait_until_completed();
+
+        let mut bytes = [0u8; 16];
+        unsafe {
+            std::ptr::copy_nonoverlapping(result.contents() as *const u8, bytes.as_mut_ptr(), 16);
+        }
+        let nonce = u128::from_le_bytes(bytes);
+        if nonce != 0 {
+            Some(nonce)
+        } else {
+            None
+        }
+    }
+}
+
+impl Worker for EaglesongMetal {
+    fn run(&mut self, progress_bar: &ProgressBar) {
+        let mut state_update_counter = 0usize;
+        let mut start = Instant::now();
+        loop {
+            self.poll_worker_message();
+            if self.start {
+                if let Some((pow_hash, target)) = self.pow_info.clone() {
+                    state_update_counter += self.solve(&pow_hash, &target);
+
+                    let elapsed = start.elapsed();
+                    if elapsed.as_millis() > STATE_UPDATE_DURATION_MILLIS {
+                        let elapsed_nanos: f64 = (elapsed.as_secs() * 1_000_000_000
+                            + u64::from(elapsed.subsec_nanos()))
+                            as f64
+                            / 1_000_000_000.0;
+                        progress_bar.set_message(&format!(
+                            "metal hash rate: {:>10.3} / seals found: {:>10}",
+                            state_update_counter as f64 / elapsed_nanos,
+                            self.seal_candidates_found,
+                        ));
+                        progress_bar.inc(1);
+                        state_update_counter = 0;
+                        start = Instant::now();
+                    }
+                }
+            } else {
+                state_update_counter = 0;
+                start = Instant::now();
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+#[cfg(feature = "gpu")]
+mod cuda;
+mod eaglesong;
+mod hashlog;
+#[cfg(feature = "apple_metal")]
+mod metal;
+#[cfg(feature = "vulkan")]
+mod vulkan;
+
+use crate::config::MinerConfig;
+use ckb_types::{packed::Byte32, U256};
+use crossbeam_channel::{Receiver, Sender};
+#[cfg(feature = "gpu")]
+use cuda::EaglesongCuda;
+use eaglesong::EaglesongCpu;
+#[cfg(feature = "apple_metal")]
+pub use metal::EaglesongMetal;
+#[cfg(feature = "vulkan")]
+pub use vulkan::EaglesongVulkan;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::thread;
+
+/// A message sent from the miner core to each worker.
+#[derive(Clone)]
+pub enum WorkerMessage {
+    Stop,
+    Start,
+    NewWork((Byte32, U256)),
+}
+
+/// A single mining backend running its own search loop on a dedicated thread.
+pub trait Worker {
+    fn run(&mut self, progress_bar: &ProgressBar);
+}
+
+/// Spawn `threads` CPU workers, each fed by a clone of `worker_rx` and posting
+/// seals on `seal_tx`, and return the `MultiProgress` driving their progress
+/// bars.
+pub fn start_worker(
+    config: &MinerConfig,
+    seal_tx: Sender<(Byte32, u128)>,
+    worker_rx: Receiver<WorkerMessage>,
+) -> MultiProgress {
+    let mp = MultiProgress::new();
+    for i in 0..config.threads {
+        let worker_name = "EaglesongCpu".to_string();
+        let pb = mp.add(ProgressBar::new(100));
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template("{prefix:.bold.dim} {spinner} {msg}")
+                .progress_chars("##-"),
+        );
+        pb.set_prefix(&worker_name);
+
+        // Give each worker its own hashlog file so their persisted ranges do not
+        // clobber one another.
+        let hashlog_path = config
+            .hashlog_path
+            .as_ref()
+            .map(|base| format!("{}.{}", base, i));
+        let mut worker = EaglesongCpu::new(
+            seal_tx.clone(),
+            worker_rx.clone(),
+            config.arch,
+            config.state_update_duration_millis,
+            hashlog_path,
+        );
+        thread::Builder::new()
+            .name(worker_name)
+            .spawn(move || {
+                worker.run(&pb);
+            })
+            .expect("Start `EaglesongCpu` worker thread failed");
+    }
+
+    // Opportunistically add a GPU worker: if the CUDA runtime and kernel load at
+    // runtime we mine on the GPU as well, otherwise we quietly stay CPU-only.
+    #[cfg(feature = "gpu")]
+    if config.gpu {
+        if let Some(mut worker) = EaglesongCuda::try_new(seal_tx.clone(), worker_rx.clone()) {
+            let worker_name = "EaglesongCuda".to_string();
+            let pb = mp.add(ProgressBar::new(100));
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .template("{prefix:.bold.dim} {spinner} {msg}")
+                    .progress_chars("##-"),
+            );
+            pb.set_prefix(&worker_name);
+            thread::Builder::new()
+                .name(worker_name)
+                .spawn(move || {
+                    worker.run(&pb);
+                })
+                .expect("Start `EaglesongCuda` worker thread failed");
+        }
+    }
+
+    // AMD/Intel GPUs via Vulkan: start the backend if a Vulkan driver and the
+    // compute pipeline come up, otherwise stay on the other workers.
+    #[cfg(feature = "vulkan")]
+    match EaglesongVulkan::new(seal_tx.clone(), worker_rx.clone()) {
+        Ok(mut worker) => {
+            let worker_name = "EaglesongVulkan".to_string();
+            let pb = mp.add(ProgressBar::new(100));
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .template("{prefix:.bold.dim} {spinner} {msg}")
+                    .progress_chars("##-"),
+            );
+            pb.set_prefix(&worker_name);
+            thread::Builder::new()
+                .name(worker_name)
+                .spawn(move || {
+                    worker.run(&pb);
+                })
+                .expect("Start `EaglesongVulkan` worker thread failed");
+        }
+        Err(err) => ckb_logger::info!("vulkan worker unavailable: {}", err),
+    }
+
+    // Apple Silicon via Metal, on the same terms.
+    #[cfg(feature = "apple_metal")]
+    match EaglesongMetal::new(seal_tx.clone(), worker_rx.clone()) {
+        Ok(mut worker) => {
+            let worker_name = "EaglesongMetal".to_string();
+            let pb = mp.add(ProgressBar::new(100));
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .template("{prefix:.bold.dim} {spinner} {msg}")
+                    .progress_chars("##-"),
+            );
+            pb.set_prefix(&worker_name);
+            thread::Builder::new()
+                .name(worker_name)
+                .spawn(move || {
+                    worker.run(&pb);
+                })
+                .expect("Start `EaglesongMetal` worker thread failed");
+        }
+        Err(err) => ckb_logger::info!("metal worker unavailable: {}", err),
+    }
+
+    mp
+}
@@ -0,0 +1,503 @@
+use super::{Worker, WorkerMessage};
+use ash::{vk, Device, Entry, Instance};
+use ckb_logger::{debug, error, info};
+use ckb_types::{packed::Byte32, prelude::*, U256};
+use crossbeam_channel::{Receiver, Sender};
+use indicatif::ProgressBar;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const STATE_UPDATE_DURATION_MILLIS: u128 = 300;
+
+/// Number of Eaglesong invocations dispatched per compute submission. Each
+/// invocation in the workgroup takes a distinct nonce offset from the base.
+const DISPATCH_SIZE: u32 = 1 << 20;
+
+/// Local workgroup size declared by `eaglesong.comp`; the dispatch hands out
+/// `DISPATCH_SIZE / LOCAL_SIZE` workgroups.
+const LOCAL_SIZE: u32 = 64;
+
+/// SPIR-V binary for the Eaglesong round function, produced from
+/// `src/worker/include/eaglesong.comp` by `build.rs` when the `vulkan` feature
+/// is enabled.
+const EAGLESONG_SPIRV: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/eaglesong.spv"));
+
+/// A GPU worker that dispatches the Eaglesong compute shader through a Vulkan
+/// compute pipeline, so AMD/Intel GPUs (anything with a Vulkan driver) can mine
+/// alongside the CPU and CUDA backends.
+pub struct EaglesongVulkan {
+    start: bool,
+    pow_info: Option<(Byte32, U256)>,
+    seal_tx: Sender<(Byte32, u128)>,
+    worker_rx: Receiver<WorkerMessage>,
+    seal_candidates_found: u64,
+    // Base nonce for the next dispatch; advanced each pass and reset on new work.
+    nonce_base: u128,
+    ctx: VulkanContext,
+}
+
+/// A host-visible buffer and its backing memory, mapped on demand to stage the
+/// shader's inputs and read its result back.
+struct GpuBuffer {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    size: vk::DeviceSize,
+}
+
+/// The long-lived Vulkan objects needed to dispatch the kernel. Created once
+/// and reused for every submission.
+struct VulkanContext {
+    _entry: Entry,
+    instance: Instance,
+    device: Device,
+    queue: vk::Queue,
+    pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    command_pool: vk::CommandPool,
+    // Bound once to the descriptor set: pow-hash, target, base nonce, result.
+    input: GpuBuffer,
+    target: GpuBuffer,
+    base: GpuBuffer,
+    result: GpuBuffer,
+}
+
+impl EaglesongVulkan {
+    pub fn new(
+        seal_tx: Sender<(Byte32, u128)>,
+        worker_rx: Receiver<WorkerMessage>,
+    ) -> Result<Self, String> {
+        let ctx = VulkanContext::new(EAGLESONG_SPIRV)?;
+        info!("eaglesong vulkan worker initialized");
+        Ok(Self {
+            start: true,
+            pow_info: None,
+            seal_tx,
+            worker_rx,
+            seal_candidates_found: 0,
+            nonce_base: 0,
+            ctx,
+        })
+    }
+
+    fn poll_worker_message(&mut self) {
+        if let Ok(msg) = self.worker_rx.try_recv() {
+            match msg {
+                WorkerMessage::NewWork(pow_info) => {
+                    self.pow_info = Some(pow_info);
+                    self.nonce_base = 0;
+                }
+                WorkerMessage::Stop => self.start = false,
+                WorkerMessage::Start => self.start = true,
+            }
+        }
+    }
+
+    fn solve(&mut self, pow_hash: &Byte32, target: &U256) -> usize {
+        let base = self.nonce_base;
+        self.nonce_base = self.nonce_base.wrapping_add(u128::from(DISPATCH_SIZE));
+        match self.ctx.dispatch(pow_hash, target, base, DISPATCH_SIZE) {
+            Ok(Some(nonce)) => {
+                debug!(
+                    "send new found seal, pow_hash {:x}, nonce {:?}",
+                    pow_hash, nonce
+                );
+                if let Err(err) = self.seal_tx.send((pow_hash.clone(), nonce)) {
+                    error!("seal_tx send error {:?}", err);
+                }
+                self.seal_candidates_found += 1;
+                DISPATCH_SIZE as usize
+            }
+            Ok(None) => DISPATCH_SIZE as usize,
+            Err(err) => {
+                error!("vulkan dispatch error {}", err);
+                0
+            }
+        }
+    }
+}
+
+impl Worker for EaglesongVulkan {
+    fn run(&mut self, progress_bar: &ProgressBar) {
+        let mut state_update_counter = 0usize;
+        let mut start = Instant::now();
+        loop {
+            self.poll_worker_message();
+            if self.start {
+                if let Some((pow_hash, target)) = self.pow_info.clone() {
+                    state_update_counter += self.solve(&pow_hash, &target);
+
+                    let elapsed = start.elapsed();
+                    if elapsed.as_millis() > STATE_UPDATE_DURATION_MILLIS {
+                        let elapsed_nanos: f64 = (elapsed.as_secs() * 1_000_000_000
+                            + u64::from(elapsed.subsec_nanos()))
+                            as f64
+                            / 1_000_000_000.0;
+                        progress_bar.set_message(&format!(
+                            "vulkan hash rate: {:>10.3} / seals found: {:>10}",
+                            state_update_counter as f64 / elapsed_nanos,
+                            self.seal_candidates_found,
+                        ));
+                        progress_bar.inc(1);
+                        state_update_counter = 0;
+                        start = Instant::now();
+                    }
+                }
+            } else {
+                state_update_counter = 0;
+                start = Instant::now();
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+impl VulkanContext {
+    fn new(spirv: &[u8]) -> Result<Self, String> {
+        unsafe {
+            let entry = Entry::load().map_err(|e| format!("load vulkan entry: {:?}", e))?;
+            let app_info = vk::ApplicationInfo::builder().api_version(vk::API_VERSION_1_1);
+            let instance = entry
+                .create_instance(
+                    &vk::InstanceCreateInfo::builder().application_info(&app_info),
+                    None,
+                )
+                .map_err(|e| format!("create instance: {:?}", e))?;
+
+            let physical = *instance
+                .enumerate_physical_devices()
+                .map_err(|e| format!("enumerate devices: {:?}", e))?
+                .first()
+                .ok_or_else(|| "no vulkan device".to_string())?;
+            let mem_properties = instance.get_physical_device_memory_properties(physical);
+
+            let queue_family = instance
+                .get_physical_device_queue_family_properties(physical)
+                .iter()
+                .position(|p| p.queue_flags.contains(vk::QueueFlags::COMPUTE))
+                .ok_or_else(|| "no compute queue".to_string())? as u32;
+
+            let queue_info = vk::DeviceQueueCreateInfo::builder()
+                .queue_family_index(queue_family)
+                .queue_priorities(&[1.0]);
+            let device = instance
+                .create_device(
+                    physical,
+                    &vk::DeviceCreateInfo::builder()
+                        .queue_create_infos(std::slice::from_ref(&queue_info)),
+                    None,
+                )
+                .map_err(|e| format!("create device: {:?}", e))?;
+            let queue = device.get_device_queue(queue_family, 0);
+
+            // Four `std430` storage buffers, in binding order: pow-hash input,
+            // target, base nonce, and the result the shader writes back.
+            let bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..4)
+                .map(|i| {
+                    vk::DescriptorSetLayoutBinding::builder()
+                        .binding(i)
+                        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                        .descriptor_count(1)
+                        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                        .build()
+                })
+                .collect();
+            let descriptor_set_layout = device
+                .create_descriptor_set_layout(
+                    &vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings),
+                    None,
+                )
+                .map_err(|e| format!("create descriptor set layout: {:?}", e))?;
+
+            let code: Vec<u32> = spirv
+                .chunks_exact(4)
+                .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect();
+            let module = device
+                .create_shader_module(&vk::ShaderModuleCreateInfo::builder().code(&code), None)
+                .map_err(|e| format!("create shader module: {:?}", e))?;
+
+            let pipeline_layout = device
+                .create_pipeline_layout(
+                    &vk::PipelineLayoutCreateInfo::builder()
+                        .set_layouts(std::slice::from_ref(&descriptor_set_layout)),
+                    None,
+                )
+                .map_err(|e| format!("create pipeline layout: {:?}", e))?;
+            let entry_name = std::ffi::CString::new("main").unwrap();
+            let stage = vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::COMPUTE)
+                .module(module)
+                .name(&entry_name);
+            let pipeline = device
+                .create_compute_pipelines(
+                    vk::PipelineCache::null(),
+                    &[vk::ComputePipelineCreateInfo::builder()
+                        .stage(stage.build())
+                        .layout(pipeline_layout)
+                        .build()],
+                    None,
+                )
+                .map_err(|(_, e)| format!("create pipeline: {:?}", e))?[0];
+            device.destroy_shader_module(module, None);
+
+            let command_pool = device
+                .create_command_pool(
+                    &vk::CommandPoolCreateInfo::builder()
+                        .queue_family_index(queue_family)
+                        .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER),
+                    None,
+                )
+                .map_err(|e| format!("create command pool: {:?}", e))?;
+
+            let input = GpuBuffer::new(&device, &mem_properties, 32)?;
+            let target = GpuBuffer::new(&device, &mem_properties, 32)?;
+            let base = GpuBuffer::new(&device, &mem_properties, 16)?;
+            let result = GpuBuffer::new(&device, &mem_properties, 16)?;
+
+            // One descriptor set, bound to the four buffers for the pipeline's
+            // lifetime; only the buffer contents change between dispatches.
+            let pool_size = vk::DescriptorPoolSize::builder()
+                .ty(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(4);
+            let descriptor_pool = device
+                .create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo::builder()
+                        .max_sets(1)
+                        .pool_sizes(std::slice::from_ref(&pool_size)),
+                    None,
+                )
+                .map_err(|e| format!("create descriptor pool: {:?}", e))?;
+            let descriptor_set = device
+                .allocate_descriptor_sets(
+                    &vk::DescriptorSetAllocateInfo::builder()
+                        .descriptor_pool(descriptor_pool)
+                        .set_layouts(std::slice::from_ref(&descriptor_set_layout)),
+                )
+                .map_err(|e| format!("allocate descriptor set: {:?}", e))?[0];
+
+            let buffer_infos = [
+                input.descriptor(),
+                target.descriptor(),
+                base.descriptor(),
+                result.descriptor(),
+            ];
+            let writes: Vec<vk::WriteDescriptorSet> = buffer_infos
+                .iter()
+                .enumerate()
+                .map(|(i, info)| {
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(descriptor_set)
+                        .dst_binding(i as u32)
+                        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                        .buffer_info(std::slice::from_ref(info))
+                        .build()
+                })
+                .collect();
+            device.update_descriptor_sets(&writes, &[]);
+
+            Ok(Self {
+                _entry: entry,
+                instance,
+                device,
+                queue,
+                pipeline,
+                pipeline_layout,
+                descriptor_set_layout,
+                descriptor_pool,
+                descriptor_set,
+                command_pool,
+                input,
+                target,
+                base,
+                result,
+            })
+        }
+    }
+
+    /// Dispatch `count` Eaglesong invocations for `pow_hash`/`target` starting at
+    /// `base`, returning the first nonce that met the target, if any.
+    fn dispatch(
+        &self,
+        pow_hash: &Byte32,
+        target: &U256,
+        base: u128,
+        count: u32,
+    ) -> Result<Option<u128>, String> {
+        unsafe {
+            // Stage the inputs and clear the result slot the shader writes into.
+            self.input.write(&self.device, pow_hash.as_slice())?;
+            self.target.write(&self.device, &target.to_be_bytes())?;
+            self.base.write(&self.device, &base.to_le_bytes())?;
+            self.result.write(&self.device, &[0u8; 16])?;
+
+            let command_buffer = self
+                .device
+                .allocate_command_buffers(
+                    &vk::CommandBufferAllocateInfo::builder()
+                        .command_pool(self.command_pool)
+                        .level(vk::CommandBufferLevel::PRIMARY)
+                        .command_buffer_count(1),
+                )
+                .map_err(|e| format!("allocate command buffer: {:?}", e))?[0];
+
+            self.device
+                .begin_command_buffer(
+                    command_buffer,
+                    &vk::CommandBufferBeginInfo::builder()
+                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                )
+                .map_err(|e| format!("begin command buffer: {:?}", e))?;
+            self.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline,
+            );
+            self.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            self.device
+                .cmd_dispatch(command_buffer, count / LOCAL_SIZE, 1, 1);
+            self.device
+                .end_command_buffer(command_buffer)
+                .map_err(|e| format!("end command buffer: {:?}", e))?;
+
+            let fence = self
+                .device
+                .create_fence(&vk::FenceCreateInfo::builder(), None)
+                .map_err(|e| format!("create fence: {:?}", e))?;
+            let submit = vk::SubmitInfo::builder()
+                .command_buffers(std::slice::from_ref(&command_buffer))
+                .build();
+            self.device
+                .queue_submit(self.queue, &[submit], fence)
+                .map_err(|e| format!("queue submit: {:?}", e))?;
+            self.device
+                .wait_for_fences(&[fence], true, u64::MAX)
+                .map_err(|e| format!("wait fence: {:?}", e))?;
+
+            self.device.destroy_fence(fence, None);
+            self.device
+                .free_command_buffers(self.command_pool, &[command_buffer]);
+
+            let mut bytes = [0u8; 16];
+            self.result.read(&self.device, &mut bytes)?;
+            let nonce = u128::from_le_bytes(bytes);
+            Ok((nonce != 0).then_some(nonce))
+        }
+    }
+}
+
+impl GpuBuffer {
+    /// Allocate a host-visible, host-coherent storage buffer of `size` bytes.
+    unsafe fn new(
+        device: &Device,
+        mem_properties: &vk::PhysicalDeviceMemoryProperties,
+        size: vk::DeviceSize,
+    ) -> Result<Self, String> {
+        let buffer = device
+            .create_buffer(
+                &vk::BufferCreateInfo::builder()
+                    .size(size)
+                    .usage(vk::BufferUsageFlags::STORAGE_BUFFER)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE),
+                None,
+            )
+            .map_err(|e| format!("create buffer: {:?}", e))?;
+        let requirements = device.get_buffer_memory_requirements(buffer);
+        let memory_type = find_memory_type(
+            mem_properties,
+            requirements.memory_type_bits,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )
+        .ok_or_else(|| "no host-visible memory type".to_string())?;
+        let memory = device
+            .allocate_memory(
+                &vk::MemoryAllocateInfo::builder()
+                    .allocation_size(requirements.size)
+                    .memory_type_index(memory_type),
+                None,
+            )
+            .map_err(|e| format!("allocate memory: {:?}", e))?;
+        device
+            .bind_buffer_memory(buffer, memory, 0)
+            .map_err(|e| format!("bind buffer memory: {:?}", e))?;
+        Ok(Self {
+            buffer,
+            memory,
+            size,
+        })
+    }
+
+    fn descriptor(&self) -> vk::DescriptorBufferInfo {
+        vk::DescriptorBufferInfo::builder()
+            .buffer(self.buffer)
+            .offset(0)
+            .range(self.size)
+            .build()
+    }
+
+    unsafe fn write(&self, device: &Device, data: &[u8]) -> Result<(), String> {
+        let ptr = device
+            .map_memory(self.memory, 0, self.size, vk::MemoryMapFlags::empty())
+            .map_err(|e| format!("map memory: {:?}", e))?;
+        std::ptr::copy_nonoverlapping(data.as_ptr(), ptr as *mut u8, data.len());
+        device.unmap_memory(self.memory);
+        Ok(())
+    }
+
+    unsafe fn read(&self, device: &Device, out: &mut [u8]) -> Result<(), String> {
+        let ptr = device
+            .map_memory(self.memory, 0, self.size, vk::MemoryMapFlags::empty())
+            .map_err(|e| format!("map memory: {:?}", e))?;
+        std::ptr::copy_nonoverlapping(ptr as *const u8, out.as_mut_ptr(), out.len());
+        device.unmap_memory(self.memory);
+        Ok(())
+    }
+
+    unsafe fn destroy(&self, device: &Device) {
+        device.destroy_buffer(self.buffer, None);
+        device.free_memory(self.memory, None);
+    }
+}
+
+fn find_memory_type(
+    properties: &vk::PhysicalDeviceMemoryProperties,
+    type_bits: u32,
+    flags: vk::MemoryPropertyFlags,
+) -> Option<u32> {
+    (0..properties.memory_type_count).find(|&i| {
+        (type_bits & (1 << i)) != 0
+            && properties.memory_types[i as usize]
+                .property_flags
+                .contains(flags)
+    })
+}
+
+impl Drop for VulkanContext {
+    fn drop(&mut self) {
+        unsafe {
+            self.input.destroy(&self.device);
+            self.target.destroy(&self.device);
+            self.base.destroy(&self.device);
+            self.result.destroy(&self.device);
+            self.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            self.device.destroy_command_pool(self.command_pool, None);
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.destroy_device(None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}